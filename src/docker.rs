@@ -0,0 +1,247 @@
+// Docker Engine API client: builds and runs images without shelling out to `docker`.
+use std::path::Path;
+
+use std::io::Read;
+
+use bollard::container::{Config, DownloadFromContainerOptions, LogsOptions, WaitContainerOptions};
+use bollard::image::BuildImageOptions;
+use bollard::models::HostConfig;
+use bollard::Docker;
+use eyre::{eyre, Result, WrapErr};
+use futures_util::stream::StreamExt;
+use sha2::{Digest, Sha256};
+
+/// Number of hex characters of the digest kept as the image tag (e.g. `ab12cd34`).
+const TAG_DIGEST_LEN: usize = 8;
+
+/// Derives a short, content-addressed tag from everything that affects the built image, so
+/// unchanged inputs reuse the same tag and different scripts never collide.
+pub fn compute_image_tag(script_contents: &[u8], modules: &[String], python_version: &str, dockerfile: &str) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(script_contents);
+    for module in modules {
+        hasher.update(module.as_bytes());
+        hasher.update(b"\0");
+    }
+    hasher.update(python_version.as_bytes());
+    hasher.update(dockerfile.as_bytes());
+    let digest = hasher.finalize();
+    hex::encode(digest)[..TAG_DIGEST_LEN].to_string()
+}
+
+/// Whether the build should proceed even if a matching tag already exists.
+pub fn force_build() -> bool {
+    std::env::var("DOCK_FORCE_BUILD").is_ok()
+}
+
+/// Connects to the daemon, honoring `DOCKER_HOST`/`DOCKER_TLS_VERIFY` env vars when set.
+///
+/// `connect_with_local_defaults` always targets the local unix socket / named pipe and
+/// ignores those env vars; `connect_with_defaults` is the one that actually picks
+/// unix/tcp/ssl based on them, which is what lets a remote engine be targeted.
+pub async fn connect() -> Result<Docker> {
+    Docker::connect_with_defaults().wrap_err("Failed to connect to the Docker daemon")
+}
+
+/// Builds an in-memory tar archive containing the script, the generated Dockerfile, and
+/// (when pip-cache-volume pre-fetching ran) the downloaded wheels under `wheelhouse/`.
+pub fn build_context_tar(script_path: &Path, script_name: &str, dockerfile: &str, wheelhouse: &[(String, Vec<u8>)]) -> Result<Vec<u8>> {
+    let mut builder = tar::Builder::new(Vec::new());
+    let script_bytes = std::fs::read(script_path)
+        .wrap_err_with(|| format!("Failed to read script at {}", script_path.display()))?;
+    append_file(&mut builder, script_name, &script_bytes)?;
+    append_file(&mut builder, "Dockerfile", dockerfile.as_bytes())?;
+    for (name, contents) in wheelhouse {
+        append_file(&mut builder, &format!("wheelhouse/{}", name), contents)?;
+    }
+    builder
+        .into_inner()
+        .wrap_err("Failed to finalize build context archive")
+}
+
+fn append_file(builder: &mut tar::Builder<Vec<u8>>, name: &str, contents: &[u8]) -> Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(contents.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder
+        .append_data(&mut header, name, contents)
+        .wrap_err_with(|| format!("Failed to add {} to build context", name))?;
+    Ok(())
+}
+
+/// Builds `tag` from `context_tar`, streaming build output to stdout as it arrives.
+pub async fn build_image(docker: &Docker, tag: &str, context_tar: Vec<u8>) -> Result<()> {
+    let options = BuildImageOptions {
+        dockerfile: "Dockerfile",
+        t: tag,
+        rm: true,
+        ..Default::default()
+    };
+
+    let mut stream = docker.build_image(options, None, Some(context_tar.into()));
+    while let Some(update) = stream.next().await {
+        let info = update.wrap_err("Docker build stream error")?;
+        if let Some(line) = info.stream {
+            print!("{}", line);
+        }
+        if let Some(error) = info.error {
+            return Err(eyre!("Docker build failed: {}", error));
+        }
+    }
+    Ok(())
+}
+
+/// Checks whether an image with `tag` already exists on the daemon.
+pub async fn image_exists(docker: &Docker, tag: &str) -> Result<bool> {
+    match docker.inspect_image(tag).await {
+        Ok(_) => Ok(true),
+        Err(bollard::errors::Error::DockerResponseServerError { status_code: 404, .. }) => Ok(false),
+        Err(err) => Err(err).wrap_err("Failed to inspect image"),
+    }
+}
+
+/// Creates, starts and streams logs for a container running `image`, then waits for it to exit.
+pub async fn run_container(docker: &Docker, image: &str, args: &[String]) -> Result<()> {
+    let config = Config {
+        image: Some(image),
+        cmd: if args.is_empty() {
+            None
+        } else {
+            Some(args.iter().map(String::as_str).collect())
+        },
+        ..Default::default()
+    };
+
+    let container = docker
+        .create_container::<&str, &str>(None, config)
+        .await
+        .wrap_err("Failed to create container")?;
+
+    docker
+        .start_container::<String>(&container.id, None)
+        .await
+        .wrap_err("Failed to start container")?;
+
+    let mut logs = docker.logs::<String>(
+        &container.id,
+        Some(LogsOptions {
+            stdout: true,
+            stderr: true,
+            follow: true,
+            ..Default::default()
+        }),
+    );
+    while let Some(chunk) = logs.next().await {
+        print!("{}", chunk.wrap_err("Error reading container logs")?);
+    }
+
+    let mut wait_stream = docker.wait_container(&container.id, None::<WaitContainerOptions<String>>);
+    if let Some(result) = wait_stream.next().await {
+        let status = result.wrap_err("Error waiting for container")?;
+        if status.status_code != 0 {
+            return Err(eyre!("Container exited with status {}", status.status_code));
+        }
+    }
+
+    Ok(())
+}
+
+/// Pre-downloads `modules` into a wheelhouse, using `cache_volume` as pip's own download
+/// cache so repeated dockerizations of similar scripts skip re-fetching wheels.
+///
+/// Fetches against `base_image` itself (the same image the final `FROM` stage uses), not a
+/// separate assumption about the interpreter, so the downloaded wheels are guaranteed
+/// compatible with whatever `pip install --no-index --find-links=...` runs against later.
+///
+/// The classic (non-BuildKit) `/build` endpoint used by [`build_image`] can't mount a
+/// volume during the build itself, so the wheels are fetched here in a throwaway
+/// container and copied into the build context instead; [`template::render`] then emits a
+/// plain `COPY`+`pip install --find-links` instead of a BuildKit cache mount.
+pub async fn populate_pip_cache(
+    docker: &Docker,
+    base_image: &str,
+    cache_volume: &str,
+    modules: &[String],
+) -> Result<Vec<(String, Vec<u8>)>> {
+    let mut cmd = vec!["pip".to_string(), "download".to_string(), "--dest".to_string(), "/wheelhouse".to_string()];
+    cmd.extend(modules.iter().cloned());
+
+    let config = Config {
+        image: Some(base_image),
+        cmd: Some(cmd.iter().map(String::as_str).collect()),
+        host_config: Some(HostConfig {
+            binds: Some(vec![format!("{}:/root/.cache/pip", cache_volume)]),
+            ..Default::default()
+        }),
+        ..Default::default()
+    };
+
+    let container = docker
+        .create_container::<&str, &str>(None, config)
+        .await
+        .wrap_err("Failed to create pip-cache container")?;
+    docker
+        .start_container::<String>(&container.id, None)
+        .await
+        .wrap_err("Failed to start pip-cache container")?;
+
+    let mut wait_stream = docker.wait_container(&container.id, None::<WaitContainerOptions<String>>);
+    if let Some(result) = wait_stream.next().await {
+        let status = result.wrap_err("Error waiting for pip-cache container")?;
+        if status.status_code != 0 {
+            docker.remove_container(&container.id, None).await.ok();
+            return Err(eyre!("pip download exited with status {}", status.status_code));
+        }
+    }
+
+    let mut archive = Vec::new();
+    let mut stream = docker.download_from_container(
+        &container.id,
+        Some(DownloadFromContainerOptions { path: "/wheelhouse" }),
+    );
+    while let Some(chunk) = stream.next().await {
+        archive.extend_from_slice(&chunk.wrap_err("Failed to download wheelhouse from container")?);
+    }
+    docker.remove_container(&container.id, None).await.ok();
+
+    let mut wheels = Vec::new();
+    let mut tar_archive = tar::Archive::new(archive.as_slice());
+    for entry in tar_archive.entries().wrap_err("Failed to read wheelhouse archive")? {
+        let mut entry = entry.wrap_err("Failed to read wheelhouse archive entry")?;
+        if !entry.header().entry_type().is_file() {
+            continue;
+        }
+        let path = entry.path().wrap_err("Failed to read wheelhouse entry path")?.into_owned();
+        let Some(file_name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let mut contents = Vec::new();
+        entry.read_to_end(&mut contents).wrap_err("Failed to read wheel contents")?;
+        wheels.push((file_name.to_string(), contents));
+    }
+
+    Ok(wheels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compute_image_tag_is_deterministic() {
+        let a = compute_image_tag(b"print(1)", &["requests==2.31.0".to_string()], "3.10", "FROM python:3.10");
+        let b = compute_image_tag(b"print(1)", &["requests==2.31.0".to_string()], "3.10", "FROM python:3.10");
+        assert_eq!(a, b);
+        assert_eq!(a.len(), TAG_DIGEST_LEN);
+    }
+
+    #[test]
+    fn compute_image_tag_changes_with_inputs() {
+        let base = compute_image_tag(b"print(1)", &["requests==2.31.0".to_string()], "3.10", "FROM python:3.10");
+        let different_script = compute_image_tag(b"print(2)", &["requests==2.31.0".to_string()], "3.10", "FROM python:3.10");
+        let different_module = compute_image_tag(b"print(1)", &["requests==2.32.0".to_string()], "3.10", "FROM python:3.10");
+        assert_ne!(base, different_script);
+        assert_ne!(base, different_module);
+    }
+}
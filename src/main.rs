@@ -1,32 +1,50 @@
-#![cfg_attr(debug_assertions, allow(unused_imports, unused_variables, unused_mut, dead_code))]
-
 // Standard library imports
 use std::path::{Path, PathBuf};
-use std::process::Command;
 
 // External crates
 use clap::Parser;
-use eyre::WrapErr;
 use eyre::{eyre, Result};
-use reqwest;
 use serde::{Deserialize, Serialize};
-use tokio::fs::File;
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 
+mod docker;
+mod pypi;
+mod python;
+mod template;
+mod verify;
+mod volumes;
+
+use clap::Subcommand;
+use python::PythonImport;
+use template::DockerfileContext;
+use volumes::VolumeCommand;
+
 #[derive(Parser, Debug)]
 #[clap(name = "dock", about = "Dockerize any Python script")]
 struct Cli {
-    #[clap(required = true, help = "Python Script")]
-    script: PathBuf,
+    #[clap(subcommand)]
+    command: Option<Commands>,
+
+    #[clap(required_unless_present = "command", help = "Python Script")]
+    script: Option<PathBuf>,
 
     #[clap(help = "Optional list of args")]
     args: Vec<String>,
+
+    #[clap(long, help = "Smoke-test the built image by importing every resolved module before running it")]
+    verify: bool,
+
+    #[clap(long, help = "Name of a data volume to cache pip downloads across builds")]
+    pip_cache_volume: Option<String>,
 }
 
-#[derive(Debug)]
-enum PythonImport {
-    ModuleOnly(String),
-    ModuleWithMember(String, String),
+#[derive(Subcommand, Debug)]
+enum Commands {
+    /// Manage the named data volumes used to cache pip downloads across builds.
+    Volumes {
+        #[clap(subcommand)]
+        action: VolumeCommand,
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -34,10 +52,16 @@ struct Config {
     defaults: Option<Defaults>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 struct Defaults {
     #[serde(rename = "import-mappings")]
     import_mappings: Option<std::collections::HashMap<String, String>>,
+    entrypoint: Option<Vec<String>>,
+    cmd: Option<Vec<String>>,
+    #[serde(rename = "base-image")]
+    base_image: Option<String>,
+    #[serde(rename = "system-packages")]
+    system_packages: Option<Vec<String>>,
 }
 
 async fn load_config() -> Result<Config> {
@@ -66,159 +90,42 @@ fn remap_modules(modules: &[String], mappings: &Option<std::collections::HashMap
         .collect()
 }
 
-async fn parse_python_file(script: &PathBuf) -> Result<Vec<PythonImport>> {
-    let mut file = File::open(script).await?;
-    let mut content = String::new();
-    file.read_to_string(&mut content).await?;
-
-    let imports: Vec<PythonImport> = content
-        .lines()
-        .filter_map(|line| {
-            let trimmed_line = line.trim();
-            if trimmed_line.starts_with("import ") {
-                Some(PythonImport::ModuleOnly(trimmed_line[7..].trim().to_string()))
-            } else if trimmed_line.starts_with("from ") {
-                let parts: Vec<&str> = trimmed_line[5..].split(" import ").collect();
-                if parts.len() == 2 {
-                    Some(PythonImport::ModuleWithMember(
-                        parts[0].trim().to_string(),
-                        parts[1].trim().to_string(),
-                    ))
-                } else {
-                    None
-                }
-            } else {
-                None
-            }
-        })
-        .collect();
-
-    Ok(imports)
-}
-
-fn get_python_builtins_stdlibs() -> Result<Vec<String>> {
-    // Python code as a Rust string
-    let python_code = r#"
-import sys
-
-# Get built-in modules
-builtin_modules = set(sys.builtin_module_names)
-
-# Get standard library modules
-standard_lib_modules = set(sys.stdlib_module_names)
-
-# Combine both
-all_default_modules = builtin_modules.union(standard_lib_modules)
-
-# Assuming all_default_modules is your original set of modules
-filtered_modules = {module for module in all_default_modules if not module.startswith('_')}
-
-for module in sorted(filtered_modules):
-    print(module)
-"#;
-
-    // Execute the Python code and capture the output
-    let output = Command::new("python3")
-        .arg("-c")
-        .arg(python_code)
-        .output()
-        .expect("Failed to execute command");
-
-    if !output.status.success() {
-        return Err(eyre::eyre!(
-            "Command execution failed with error: {}",
-            String::from_utf8_lossy(&output.stderr)
-        ));
-    }
-
-    let output_str = std::str::from_utf8(&output.stdout)?;
-    Ok(output_str.lines().map(|s| s.to_string()).collect())
-}
-
-/*
-async fn check_package_exists(package: &str) -> bool {
-    let url = format!("https://pypi.org/pypi/{}/json", package);
-    match reqwest::get(&url).await {
-        Ok(resp) => resp.status().is_success(),
-        Err(_) => false,
-    }
-}
-*/
-
-async fn check_package_exists(package: &str) -> Option<String> {
-    // First, try the full package name
-    let url = format!("https://pypi.org/pypi/{}/json", package);
-    if let Ok(resp) = reqwest::get(&url).await {
-        if resp.status().is_success() {
-            return Some(package.to_string());
-        }
-    }
-
-    // Fallback to the root package name
-    let root_package: &str = package.split('.').next().unwrap();
-    let url = format!("https://pypi.org/pypi/{}/json", root_package);
-    if let Ok(resp) = reqwest::get(&url).await {
-        if resp.status().is_success() {
-            return Some(root_package.to_string());
-        }
-    }
-
-    None
-}
-
-async fn generate_dockerfile(
-    python_version: &str,
-    modules: &[String],
-    script_name: &str,
-    output_dir: &Path,
-) -> Result<()> {
-    // Read the Dockerfile.template into a String
-    let default_template = r#"
-FROM python:{{PYTHON_VERSION}}
-
-RUN useradd -ms /bin/bash dock
-USER dock
-
-RUN pip install {{MODULES}}
-
-COPY {{SCRIPT_NAME}} /home/dock/{{SCRIPT_NAME}}
-WORKDIR /home/dock
-
-ENTRYPOINT ["python3", "{{SCRIPT_NAME}}"]
-"#;
-
-    let template = std::env::var("DOCKERFILE_TEMPLATE")
-        .ok()
-        .and_then(|path| std::fs::read_to_string(path).ok())
-        .unwrap_or_else(|| default_template.to_string());
-
-    // Replace placeholders with actual values
-    let filled_template = template
-        .replace("{{PYTHON_VERSION}}", python_version)
-        .replace("{{MODULES}}", &modules.join(" "))
-        .replace("{{SCRIPT_NAME}}", script_name);
-
-    // Write the filled template to the output Dockerfile
+async fn write_dockerfile(context: &DockerfileContext, output_dir: &Path) -> Result<()> {
+    let rendered = template::render(context)?;
     let dockerfile_path = output_dir.join("Dockerfile");
     let mut output_file = tokio::fs::File::create(&dockerfile_path).await?;
-    output_file.write_all(filled_template.as_bytes()).await?;
-
+    output_file.write_all(rendered.as_bytes()).await?;
     Ok(())
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let config = load_config().await?;
     let cli: Cli = Cli::parse();
-    let builtin_stdlibs = get_python_builtins_stdlibs()?;
-    let imports = parse_python_file(&cli.script).await?;
+
+    if let Some(Commands::Volumes { action }) = &cli.command {
+        let client = docker::connect().await?;
+        return match action {
+            VolumeCommand::List => volumes::list(&client).await,
+            VolumeCommand::Prune => volumes::prune(&client).await,
+        };
+    }
+
+    let script = cli.script.clone().ok_or_else(|| eyre!("Python script is required"))?;
+
+    let config = load_config().await?;
+    let builtin_stdlibs = python::get_python_builtins_stdlibs()?;
+    let imports = python::parse_python_file(&script).await?;
     let mut modules = Vec::new();
+    // Versions discovered while just confirming a module exists on PyPI, kept so the pin
+    // pass below doesn't re-query PyPI for the same distribution a second time.
+    let mut discovered_versions: std::collections::HashMap<String, String> = std::collections::HashMap::new();
 
     for import in imports {
         match import {
             PythonImport::ModuleOnly(module) => {
                 if !builtin_stdlibs.contains(&module) {
-                    if let Some(valid_module) = check_package_exists(&module).await {
+                    if let Some((valid_module, version)) = pypi::check_package_exists(&module).await {
+                        discovered_versions.insert(valid_module.clone(), version);
                         modules.push(valid_module);
                     }
                 }
@@ -226,9 +133,11 @@ async fn main() -> Result<()> {
             PythonImport::ModuleWithMember(module, object) => {
                 if !builtin_stdlibs.contains(&module) {
                     let full_name = format!("{}.{}", &module, &object);
-                    if let Some(valid_module) = check_package_exists(&full_name).await {
+                    if let Some((valid_module, version)) = pypi::check_package_exists(&full_name).await {
+                        discovered_versions.insert(valid_module.clone(), version);
                         modules.push(valid_module);
-                    } else if let Some(valid_module) = check_package_exists(&module).await {
+                    } else if let Some((valid_module, version)) = pypi::check_package_exists(&module).await {
+                        discovered_versions.insert(valid_module.clone(), version);
                         modules.push(valid_module);
                     }
                 }
@@ -241,35 +150,85 @@ async fn main() -> Result<()> {
     modules.dedup();
 
     // Remap modules using the import_mappings from the config
-    let remapped_modules = remap_modules(&modules, &config.defaults.and_then(|d| d.import_mappings));
+    let defaults = config.defaults.unwrap_or_default();
+    let remapped_modules = remap_modules(&modules, &defaults.import_mappings);
     let python_version = "3.10";
-    let script_name = cli
-        .script
+    let script_name = script
         .file_name()
         .ok_or(eyre!("Failed to get file name"))?
         .to_str()
         .ok_or(eyre!("Failed to convert to str"))?;
 
-    let script_path = cli.script.parent().ok_or(eyre!("Failed to get parent directory"))?;
-
-    generate_dockerfile(python_version, &remapped_modules, script_name, &script_path).await?;
-
-    Command::new("docker")
-        .env("DOCKER_BUILDKIT", "1")
-        .args(&[
-            "build",
-            "-t",
-            script_name,
-            script_path.to_str().ok_or(eyre!("Failed to convert path to str"))?,
-        ])
-        .status()
-        .wrap_err("Failed to build Docker image")?;
-
-    Command::new("docker")
-        .args(&["run", script_name])
-        .args(&cli.args)
-        .status()
-        .wrap_err("Failed to run Docker container")?;
+    let script_path = script.parent().ok_or(eyre!("Failed to get parent directory"))?;
+
+    let client = docker::connect().await?;
+
+    // Pin each module to an exact version, reusing pyze.lock when a pin already exists.
+    let lockfile_path = script_path.join("pyze.lock");
+    let mut lockfile = pypi::Lockfile::load(&lockfile_path).await?;
+    let mut pinned_modules = Vec::new();
+    for module in &remapped_modules {
+        let version = match lockfile.packages.get(module) {
+            Some(version) => version.clone(),
+            None => match discovered_versions.get(module) {
+                Some(version) => version.clone(),
+                None => {
+                    let (_, version) = pypi::check_package_exists(module)
+                        .await
+                        .ok_or_else(|| eyre!("Failed to resolve a version for {} on PyPI", module))?;
+                    version
+                }
+            },
+        };
+        lockfile.packages.insert(module.clone(), version.clone());
+        pinned_modules.push(format!("{}=={}", module, version));
+    }
+    lockfile.save(&lockfile_path).await?;
+
+    let base_image = defaults.base_image.clone().unwrap_or_else(|| format!("python:{}", python_version));
+
+    let dockerfile_context = DockerfileContext {
+        python_version: python_version.to_string(),
+        modules: pinned_modules.clone(),
+        script_name: script_name.to_string(),
+        base_image: base_image.clone(),
+        entrypoint: defaults.entrypoint.clone().unwrap_or_else(|| vec!["python3".to_string(), script_name.to_string()]),
+        cmd: defaults.cmd.clone(),
+        system_packages: defaults.system_packages.clone().unwrap_or_default(),
+        has_wheelhouse: cli.pip_cache_volume.is_some(),
+    };
+    write_dockerfile(&dockerfile_context, script_path).await?;
+
+    let dockerfile_contents = tokio::fs::read_to_string(script_path.join("Dockerfile")).await?;
+    let script_contents = tokio::fs::read(&script).await?;
+    let digest = docker::compute_image_tag(&script_contents, &pinned_modules, python_version, &dockerfile_contents);
+    let tag = format!("{}:{}", script_name, digest);
+
+    if docker::force_build() || !docker::image_exists(&client, &tag).await? {
+        // Pre-fetch wheels into the build context using the cache volume as pip's download
+        // cache, since the classic builder can't mount a volume during the build itself. Done
+        // only once we know the build is actually going to run, so an unchanged script whose
+        // image already exists skips the container round trip entirely.
+        let wheelhouse = match &cli.pip_cache_volume {
+            Some(volume) => {
+                volumes::ensure_volume(&client, volume).await?;
+                docker::populate_pip_cache(&client, &base_image, volume, &pinned_modules).await?
+            }
+            None => Vec::new(),
+        };
+
+        let context_tar = docker::build_context_tar(&script, script_name, &dockerfile_contents, &wheelhouse)?;
+        docker::build_image(&client, &tag, context_tar).await?;
+    } else {
+        println!("Image {} already exists, skipping build (set DOCK_FORCE_BUILD to rebuild)", tag);
+    }
+
+    if cli.verify {
+        let manifest = verify::verify_image(&client, &tag, &pinned_modules, script_path).await?;
+        verify::ensure_passed(&manifest)?;
+    }
+
+    docker::run_container(&client, &tag, &cli.args).await?;
 
     Ok(())
 }
@@ -0,0 +1,187 @@
+// PyPI lookups and the pyze.lock resolved-version cache.
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::path::Path;
+
+use eyre::{Result, WrapErr};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Deserialize)]
+struct PypiResponse {
+    info: PypiInfo,
+    releases: HashMap<String, Vec<PypiReleaseFile>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct PypiInfo {
+    version: String,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct PypiReleaseFile {
+    #[serde(default)]
+    yanked: bool,
+}
+
+/// The `name -> version` map persisted as `pyze.lock` next to the script, so subsequent
+/// builds reuse the exact pins instead of re-resolving against PyPI.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Lockfile {
+    pub packages: HashMap<String, String>,
+}
+
+impl Lockfile {
+    pub async fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let contents = tokio::fs::read_to_string(path)
+            .await
+            .wrap_err_with(|| format!("Failed to read {}", path.display()))?;
+        serde_yaml::from_str(&contents).wrap_err_with(|| format!("Failed to parse {}", path.display()))
+    }
+
+    pub async fn save(&self, path: &Path) -> Result<()> {
+        let contents = serde_yaml::to_string(self)?;
+        tokio::fs::write(path, contents)
+            .await
+            .wrap_err_with(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+/// Checks whether `package` exists on PyPI, returning the distribution name that was
+/// actually found (trying the full name first, then its root package) and the latest
+/// non-yanked stable release for that distribution, or PyPI's `info.version` when it has
+/// no such release.
+pub async fn check_package_exists(package: &str) -> Option<(String, String)> {
+    if let Some(version) = latest_stable_version(package).await {
+        return Some((package.to_string(), version));
+    }
+
+    let root_package = package.split('.').next().unwrap();
+    if let Some(version) = latest_stable_version(root_package).await {
+        return Some((root_package.to_string(), version));
+    }
+
+    None
+}
+
+async fn latest_stable_version(package: &str) -> Option<String> {
+    let url = format!("https://pypi.org/pypi/{}/json", package);
+    let resp = reqwest::get(&url).await.ok()?;
+    if !resp.status().is_success() {
+        return None;
+    }
+    let parsed: PypiResponse = resp.json().await.ok()?;
+
+    let mut stable_versions: Vec<&String> = parsed
+        .releases
+        .iter()
+        .filter(|(version, files)| !is_prerelease(version) && files.iter().any(|file| !file.yanked))
+        .map(|(version, _)| version)
+        .collect();
+
+    if stable_versions.is_empty() {
+        // Nothing stable and unyanked; fall back to whatever PyPI calls the current version.
+        return Some(parsed.info.version);
+    }
+
+    stable_versions.sort_by(|a, b| compare_versions(a, b));
+    stable_versions.last().map(|version| version.to_string())
+}
+
+/// Whether `version` carries a PEP 440 pre-release/dev suffix (`1.0a1`, `2.0rc1`,
+/// `1.0.dev1`, ...). The local version label after `+` (e.g. `1.0+abc`) is ignored, since
+/// it doesn't affect pre-release status and would otherwise false-positive on a bare
+/// substring match against letters like "a"/"b".
+fn is_prerelease(version: &str) -> bool {
+    let public_version = version.split('+').next().unwrap_or(version).to_lowercase();
+    ["a", "b", "rc", "dev", "pre"].iter().any(|marker| {
+        public_version.find(marker).is_some_and(|pos| {
+            matches!(public_version[..pos].chars().next_back(), None | Some('0'..='9') | Some('.') | Some('-'))
+        })
+    })
+}
+
+/// Splits a PEP 440 version into `(epoch, release segments, post-release number)`. The local
+/// version label (after `+`) is dropped, since [`is_prerelease`] already keeps dev/pre-release
+/// versions out of `stable_versions` and the local label doesn't otherwise affect ordering here.
+/// A missing post-release is represented as `-1` so a bare release always sorts before its
+/// own `postN`.
+fn parse_version(version: &str) -> (u64, Vec<u64>, i64) {
+    let public_version = version.split('+').next().unwrap_or(version);
+    let (epoch_str, rest) = public_version.split_once('!').unwrap_or(("0", public_version));
+    let epoch = epoch_str.parse().unwrap_or(0);
+
+    let lower = rest.to_lowercase();
+    let (release_part, post) = if let Some(pos) = lower.find("post") {
+        let digits: String = lower[pos + "post".len()..].chars().take_while(char::is_ascii_digit).collect();
+        (&rest[..pos], digits.parse().unwrap_or(0))
+    } else if let Some(pos) = rest.rfind('-').filter(|&pos| !rest[pos + 1..].is_empty() && rest[pos + 1..].chars().all(|c| c.is_ascii_digit())) {
+        (&rest[..pos], rest[pos + 1..].parse().unwrap_or(0))
+    } else {
+        (rest, -1)
+    };
+
+    let release = release_part.split('.').filter_map(|p| p.parse().ok()).collect();
+    (epoch, release, post)
+}
+
+/// Orders two PEP 440 version strings, comparing epoch first, then release segments
+/// (numerically, padding the shorter one with zeros so `1.0` == `1.0.0`), then the
+/// post-release number. This is what lets `stable_versions.sort_by(...).last()` pick a
+/// genuinely-latest, reproducible version instead of an arbitrary tie winner.
+fn compare_versions(a: &str, b: &str) -> Ordering {
+    let (epoch_a, release_a, post_a) = parse_version(a);
+    let (epoch_b, release_b, post_b) = parse_version(b);
+
+    epoch_a.cmp(&epoch_b).then_with(|| {
+        let len = release_a.len().max(release_b.len());
+        (0..len)
+            .map(|i| release_a.get(i).copied().unwrap_or(0).cmp(&release_b.get(i).copied().unwrap_or(0)))
+            .find(|ordering| *ordering != Ordering::Equal)
+            .unwrap_or(Ordering::Equal)
+    }).then_with(|| post_a.cmp(&post_b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn is_prerelease_detects_pep440_suffixes() {
+        assert!(is_prerelease("1.0a1"));
+        assert!(is_prerelease("2.0b1"));
+        assert!(is_prerelease("1.0rc1"));
+        assert!(is_prerelease("1.0.dev1"));
+    }
+
+    #[test]
+    fn is_prerelease_ignores_local_version_labels() {
+        assert!(!is_prerelease("1.0"));
+        assert!(!is_prerelease("1.0+abc"));
+    }
+
+    #[test]
+    fn compare_versions_orders_numerically() {
+        assert_eq!(compare_versions("2.9.0", "2.10.0"), Ordering::Less);
+        assert_eq!(compare_versions("1.0.0", "1.0.0"), Ordering::Equal);
+    }
+
+    #[test]
+    fn compare_versions_pads_differing_length_release_segments() {
+        assert_eq!(compare_versions("1.0", "1.0.0"), Ordering::Equal);
+        assert_eq!(compare_versions("1.0", "1.0.1"), Ordering::Less);
+    }
+
+    #[test]
+    fn compare_versions_breaks_ties_on_post_release() {
+        assert_eq!(compare_versions("1.0.0", "1.0.0.post1"), Ordering::Less);
+        assert_eq!(compare_versions("1.0.0.post1", "1.0.0.post2"), Ordering::Less);
+    }
+
+    #[test]
+    fn compare_versions_respects_epoch_over_release_segments() {
+        assert_eq!(compare_versions("1!1.0", "2.0"), Ordering::Greater);
+    }
+}
@@ -0,0 +1,185 @@
+// Python source inspection: import extraction and stdlib/builtin discovery.
+use std::path::PathBuf;
+use std::process::Command;
+
+use eyre::{eyre, Result, WrapErr};
+use rustpython_parser::{ast, Parse};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PythonImport {
+    ModuleOnly(String),
+    ModuleWithMember(String, String),
+}
+
+/// Parses `script` with a real Python AST and collects its imports, including ones nested
+/// inside functions, classes, conditionals and try/except blocks. Relative imports are
+/// dropped and only the top-level package segment of each module path is kept, since that's
+/// what PyPI distributions are named after.
+pub async fn parse_python_file(script: &PathBuf) -> Result<Vec<PythonImport>> {
+    let source = tokio::fs::read_to_string(script)
+        .await
+        .wrap_err_with(|| format!("Failed to read {}", script.display()))?;
+
+    let suite = ast::Suite::parse(&source, &script.to_string_lossy())
+        .map_err(|err| eyre!("Failed to parse {}: {}", script.display(), err))?;
+
+    let mut imports = Vec::new();
+    collect_imports(&suite, &mut imports);
+    Ok(imports)
+}
+
+fn collect_imports(body: &[ast::Stmt], imports: &mut Vec<PythonImport>) {
+    for stmt in body {
+        match stmt {
+            ast::Stmt::Import(import) => {
+                for alias in &import.names {
+                    imports.push(PythonImport::ModuleOnly(top_level_package(alias.name.as_str())));
+                }
+            }
+            ast::Stmt::ImportFrom(import_from) => {
+                if import_from.level.map(|level| level.to_usize() > 0).unwrap_or(false) {
+                    continue;
+                }
+                let Some(module) = &import_from.module else {
+                    continue;
+                };
+                let module_name = top_level_package(module.as_str());
+                for alias in &import_from.names {
+                    imports.push(PythonImport::ModuleWithMember(module_name.clone(), alias.name.to_string()));
+                }
+            }
+            ast::Stmt::FunctionDef(def) => collect_imports(&def.body, imports),
+            ast::Stmt::AsyncFunctionDef(def) => collect_imports(&def.body, imports),
+            ast::Stmt::ClassDef(def) => collect_imports(&def.body, imports),
+            ast::Stmt::If(node) => {
+                collect_imports(&node.body, imports);
+                collect_imports(&node.orelse, imports);
+            }
+            ast::Stmt::While(node) => {
+                collect_imports(&node.body, imports);
+                collect_imports(&node.orelse, imports);
+            }
+            ast::Stmt::For(node) => {
+                collect_imports(&node.body, imports);
+                collect_imports(&node.orelse, imports);
+            }
+            ast::Stmt::AsyncFor(node) => {
+                collect_imports(&node.body, imports);
+                collect_imports(&node.orelse, imports);
+            }
+            ast::Stmt::With(node) => collect_imports(&node.body, imports),
+            ast::Stmt::AsyncWith(node) => collect_imports(&node.body, imports),
+            ast::Stmt::Try(node) => {
+                collect_imports(&node.body, imports);
+                for handler in &node.handlers {
+                    let ast::ExceptHandler::ExceptHandler(handler) = handler;
+                    collect_imports(&handler.body, imports);
+                }
+                collect_imports(&node.orelse, imports);
+                collect_imports(&node.finalbody, imports);
+            }
+            _ => {}
+        }
+    }
+}
+
+fn top_level_package(path: &str) -> String {
+    path.split('.').next().unwrap_or(path).to_string()
+}
+
+/// Returns every built-in and standard-library module name for the `python3` on PATH, so
+/// the caller can skip resolving those against PyPI.
+pub fn get_python_builtins_stdlibs() -> Result<Vec<String>> {
+    let python_code = r#"
+import sys
+
+# Get built-in modules
+builtin_modules = set(sys.builtin_module_names)
+
+# Get standard library modules
+standard_lib_modules = set(sys.stdlib_module_names)
+
+# Combine both
+all_default_modules = builtin_modules.union(standard_lib_modules)
+
+# Assuming all_default_modules is your original set of modules
+filtered_modules = {module for module in all_default_modules if not module.startswith('_')}
+
+for module in sorted(filtered_modules):
+    print(module)
+"#;
+
+    let output = Command::new("python3")
+        .arg("-c")
+        .arg(python_code)
+        .output()
+        .expect("Failed to execute command");
+
+    if !output.status.success() {
+        return Err(eyre!(
+            "Command execution failed with error: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ));
+    }
+
+    let output_str = std::str::from_utf8(&output.stdout)?;
+    Ok(output_str.lines().map(|s| s.to_string()).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn imports_in(source: &str) -> Vec<PythonImport> {
+        let suite = ast::Suite::parse(source, "<test>").expect("test source should parse");
+        let mut imports = Vec::new();
+        collect_imports(&suite, &mut imports);
+        imports
+    }
+
+    #[test]
+    fn top_level_package_keeps_only_the_first_segment() {
+        assert_eq!(top_level_package("numpy"), "numpy");
+        assert_eq!(top_level_package("a.b.c"), "a");
+    }
+
+    #[test]
+    fn handles_comma_lists_and_aliases() {
+        let imports = imports_in("import os, sys\nimport numpy as np\n");
+        assert_eq!(
+            imports,
+            vec![
+                PythonImport::ModuleOnly("os".to_string()),
+                PythonImport::ModuleOnly("sys".to_string()),
+                PythonImport::ModuleOnly("numpy".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn handles_parenthesized_from_import_and_drops_relative_imports() {
+        let imports = imports_in("from pkg import (a, b)\nfrom . import sibling\nfrom .pkg import x\n");
+        assert_eq!(
+            imports,
+            vec![
+                PythonImport::ModuleWithMember("pkg".to_string(), "a".to_string()),
+                PythonImport::ModuleWithMember("pkg".to_string(), "b".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_imports_nested_in_functions_and_try_blocks() {
+        let imports = imports_in(
+            "def f():\n    import json\n\ntry:\n    import simplejson\nexcept ImportError:\n    import json as simplejson\n",
+        );
+        assert_eq!(
+            imports,
+            vec![
+                PythonImport::ModuleOnly("json".to_string()),
+                PythonImport::ModuleOnly("simplejson".to_string()),
+                PythonImport::ModuleOnly("json".to_string()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,105 @@
+// Dockerfile rendering via Handlebars, driven by a typed context instead of string-replace.
+use eyre::{Result, WrapErr};
+use handlebars::Handlebars;
+use serde::Serialize;
+
+const DEFAULT_TEMPLATE: &str = r#"FROM {{base_image}}
+{{#each system_packages}}
+RUN apt-get update && apt-get install -y {{this}} && rm -rf /var/lib/apt/lists/*
+{{/each}}
+RUN useradd -ms /bin/bash dock
+USER dock
+
+{{#if has_wheelhouse}}
+COPY wheelhouse /home/dock/wheelhouse
+RUN pip install --no-index --find-links=/home/dock/wheelhouse {{#each modules}}{{this}} {{/each}}
+{{else}}
+RUN pip install {{#each modules}}{{this}} {{/each}}
+{{/if}}
+
+COPY {{script_name}} /home/dock/{{script_name}}
+WORKDIR /home/dock
+
+ENTRYPOINT [{{#each entrypoint}}"{{this}}"{{#unless @last}}, {{/unless}}{{/each}}]
+{{#if cmd}}
+CMD [{{#each cmd}}"{{this}}"{{#unless @last}}, {{/unless}}{{/each}}]
+{{/if}}
+"#;
+
+/// Render context for the Dockerfile template. Of these fields, `base_image`, `entrypoint`,
+/// `cmd` and `system_packages` can be overridden via `~/.config/pyze/pyze.yml`; the rest are
+/// derived from the script and the command line.
+#[derive(Debug, Clone, Serialize)]
+pub struct DockerfileContext {
+    pub python_version: String,
+    pub modules: Vec<String>,
+    pub script_name: String,
+    pub base_image: String,
+    pub entrypoint: Vec<String>,
+    pub cmd: Option<Vec<String>>,
+    pub system_packages: Vec<String>,
+    /// Whether a `wheelhouse/` directory of pre-downloaded wheels was added to the build
+    /// context (see `docker::populate_pip_cache`), so pip should install from it offline
+    /// instead of hitting the network.
+    pub has_wheelhouse: bool,
+}
+
+/// Renders the Dockerfile, preferring a user-supplied template at `$DOCKERFILE_TEMPLATE`
+/// over the built-in default.
+pub fn render(context: &DockerfileContext) -> Result<String> {
+    let template = std::env::var("DOCKERFILE_TEMPLATE")
+        .ok()
+        .and_then(|path| std::fs::read_to_string(path).ok())
+        .unwrap_or_else(|| DEFAULT_TEMPLATE.to_string());
+
+    let mut handlebars = Handlebars::new();
+    handlebars.set_strict_mode(true);
+    // Handlebars defaults to HTML escaping (e.g. `=` becomes `&#x3D;`), which corrupts a
+    // Dockerfile target; this isn't an HTML output, so disable it.
+    handlebars.register_escape_fn(handlebars::no_escape);
+    handlebars
+        .render_template(&template, context)
+        .wrap_err("Failed to render Dockerfile template")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn context() -> DockerfileContext {
+        DockerfileContext {
+            python_version: "3.10".to_string(),
+            modules: vec!["requests==2.31.0".to_string()],
+            script_name: "script.py".to_string(),
+            base_image: "python:3.10".to_string(),
+            entrypoint: vec!["python3".to_string(), "script.py".to_string()],
+            cmd: None,
+            system_packages: Vec::new(),
+            has_wheelhouse: false,
+        }
+    }
+
+    #[test]
+    fn render_does_not_html_escape_version_pins() {
+        let rendered = render(&context()).unwrap();
+        assert!(rendered.contains("requests==2.31.0"), "rendered Dockerfile was:\n{}", rendered);
+        assert!(!rendered.contains("&#x3D;"));
+    }
+
+    #[test]
+    fn render_uses_base_image_and_entrypoint() {
+        let rendered = render(&context()).unwrap();
+        assert!(rendered.contains("FROM python:3.10"));
+        assert!(rendered.contains("ENTRYPOINT [\"python3\", \"script.py\"]"));
+    }
+
+    #[test]
+    fn render_installs_from_wheelhouse_when_pre_populated() {
+        let mut ctx = context();
+        ctx.has_wheelhouse = true;
+        let rendered = render(&ctx).unwrap();
+        assert!(rendered.contains("COPY wheelhouse /home/dock/wheelhouse"));
+        assert!(rendered.contains("--find-links=/home/dock/wheelhouse"));
+        assert!(!rendered.contains("--mount=type=cache"));
+    }
+}
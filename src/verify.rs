@@ -0,0 +1,171 @@
+// Post-build smoke test: import every resolved module inside the built image and run
+// `pip check`, so a bad PyPI-name guess in pypi::check_package_exists fails loudly instead
+// of shipping a broken image.
+use std::path::Path;
+
+use bollard::container::{Config, LogsOptions, WaitContainerOptions};
+use bollard::Docker;
+use eyre::{eyre, Result, WrapErr};
+use futures_util::stream::StreamExt;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportCheck {
+    pub module: String,
+    pub passed: bool,
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifyManifest {
+    pub image: String,
+    pub imports: Vec<ImportCheck>,
+    pub pip_check_passed: bool,
+    pub pip_check_output: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct SmokeTestResult {
+    imports: Vec<ImportCheck>,
+    pip_check_passed: bool,
+    pip_check_output: String,
+}
+
+/// Builds a short Python program (passed via `python3 -c`) that imports every module and runs `pip check`,
+/// printing the results as a single line of JSON.
+fn smoke_test_script(modules: &[String]) -> String {
+    let import_names: Vec<String> = modules
+        .iter()
+        .map(|module| module.split("==").next().unwrap_or(module).to_string())
+        .collect();
+
+    format!(
+        "import json, subprocess, sys\n\
+         results = []\n\
+         for name in {modules}:\n\
+         \ttry:\n\
+         \t\t__import__(name)\n\
+         \t\tresults.append({{'module': name, 'passed': True, 'error': None}})\n\
+         \texcept Exception as e:\n\
+         \t\tresults.append({{'module': name, 'passed': False, 'error': str(e)}})\n\
+         check = subprocess.run([sys.executable, '-m', 'pip', 'check'], capture_output=True, text=True)\n\
+         print(json.dumps({{'imports': results, 'pip_check_passed': check.returncode == 0, 'pip_check_output': check.stdout + check.stderr}}))\n",
+        modules = PyList(&import_names),
+    )
+}
+
+/// Renders a Rust string slice as a Python list literal.
+struct PyList<'a>(&'a [String]);
+
+impl std::fmt::Display for PyList<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[")?;
+        for (index, item) in self.0.iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{:?}", item)?;
+        }
+        write!(f, "]")
+    }
+}
+
+/// Runs the smoke test inside a throwaway container and writes `verify.json` next to the
+/// Dockerfile so CI can consume the per-import pass/fail report.
+pub async fn verify_image(docker: &Docker, image: &str, modules: &[String], output_dir: &Path) -> Result<VerifyManifest> {
+    let script = smoke_test_script(modules);
+    let config = Config {
+        image: Some(image),
+        entrypoint: Some(vec!["python3"]),
+        cmd: Some(vec!["-c", &script]),
+        ..Default::default()
+    };
+
+    let container = docker
+        .create_container::<&str, &str>(None, config)
+        .await
+        .wrap_err("Failed to create verification container")?;
+    docker
+        .start_container::<String>(&container.id, None)
+        .await
+        .wrap_err("Failed to start verification container")?;
+
+    let mut logs = docker.logs::<String>(
+        &container.id,
+        Some(LogsOptions {
+            stdout: true,
+            stderr: true,
+            follow: true,
+            ..Default::default()
+        }),
+    );
+    let mut output = String::new();
+    while let Some(chunk) = logs.next().await {
+        output.push_str(&chunk.wrap_err("Error reading verification logs")?.to_string());
+    }
+
+    let mut wait_stream = docker.wait_container(&container.id, None::<WaitContainerOptions<String>>);
+    if let Some(result) = wait_stream.next().await {
+        result.wrap_err("Error waiting for verification container")?;
+    }
+    docker.remove_container(&container.id, None).await.ok();
+
+    let result: SmokeTestResult =
+        serde_json::from_str(output.trim()).wrap_err("Failed to parse verification output as JSON")?;
+
+    let manifest = VerifyManifest {
+        image: image.to_string(),
+        imports: result.imports,
+        pip_check_passed: result.pip_check_passed,
+        pip_check_output: result.pip_check_output,
+    };
+
+    let manifest_path = output_dir.join("verify.json");
+    tokio::fs::write(&manifest_path, serde_json::to_string_pretty(&manifest)?)
+        .await
+        .wrap_err_with(|| format!("Failed to write {}", manifest_path.display()))?;
+
+    Ok(manifest)
+}
+
+/// Turns a manifest with any failing import or a failing `pip check` into a reportable error.
+pub fn ensure_passed(manifest: &VerifyManifest) -> Result<()> {
+    let failed: Vec<&ImportCheck> = manifest.imports.iter().filter(|check| !check.passed).collect();
+    if failed.is_empty() && manifest.pip_check_passed {
+        return Ok(());
+    }
+
+    let mut report = format!("Image verification failed for {}:\n", manifest.image);
+    for check in &failed {
+        report.push_str(&format!(
+            "  - import {} failed: {}\n",
+            check.module,
+            check.error.as_deref().unwrap_or("unknown error")
+        ));
+    }
+    if !manifest.pip_check_passed {
+        report.push_str(&format!("  - pip check reported conflicts:\n{}\n", manifest.pip_check_output));
+    }
+    Err(eyre!(report))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn py_list_renders_a_python_list_literal() {
+        let names = vec!["requests".to_string(), "numpy".to_string()];
+        assert_eq!(PyList(&names).to_string(), "[\"requests\", \"numpy\"]");
+    }
+
+    #[test]
+    fn smoke_test_script_strips_version_pins_and_is_valid_python_syntax() {
+        let script = smoke_test_script(&["requests==2.31.0".to_string()]);
+        assert!(script.contains("[\"requests\"]"));
+        assert!(!script.contains("requests==2.31.0"));
+        assert!(script.contains("pip_check_passed"));
+        let parsed = <rustpython_parser::ast::Suite as rustpython_parser::Parse>::parse(&script, "<smoke-test>");
+        assert!(parsed.is_ok(), "generated script:\n{}", script);
+    }
+}
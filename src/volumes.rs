@@ -0,0 +1,73 @@
+// Named pip-cache volumes: lets repeated builds against a remote Docker engine (no local
+// bind mounts available) reuse downloaded wheels instead of re-fetching them every time.
+use std::collections::HashMap;
+
+use bollard::volume::{ListVolumesOptions, PruneVolumesOptions};
+use bollard::Docker;
+use clap::Subcommand;
+use eyre::{Result, WrapErr};
+
+/// Label pyze stamps on every pip-cache volume it creates, so `list`/`prune` only ever
+/// touch volumes pyze itself manages.
+const MANAGED_LABEL: &str = "pyze.pip-cache";
+
+#[derive(Subcommand, Debug)]
+pub enum VolumeCommand {
+    /// List pip-cache volumes managed by pyze.
+    List,
+    /// Remove pip-cache volumes managed by pyze that no container is using.
+    Prune,
+}
+
+/// Creates `name` as a pip-cache volume if it doesn't already exist.
+pub async fn ensure_volume(docker: &Docker, name: &str) -> Result<()> {
+    if docker.inspect_volume(name).await.is_ok() {
+        return Ok(());
+    }
+
+    let mut labels = HashMap::new();
+    labels.insert(MANAGED_LABEL.to_string(), "true".to_string());
+
+    docker
+        .create_volume(bollard::volume::CreateVolumeOptions {
+            name: name.to_string(),
+            labels,
+            ..Default::default()
+        })
+        .await
+        .wrap_err_with(|| format!("Failed to create pip-cache volume {}", name))?;
+
+    Ok(())
+}
+
+/// Prints every pip-cache volume pyze manages.
+pub async fn list(docker: &Docker) -> Result<()> {
+    let mut filters = HashMap::new();
+    filters.insert("label".to_string(), vec![MANAGED_LABEL.to_string()]);
+
+    let response = docker
+        .list_volumes(Some(ListVolumesOptions { filters }))
+        .await
+        .wrap_err("Failed to list volumes")?;
+
+    for volume in response.volumes.unwrap_or_default() {
+        println!("{}", volume.name);
+    }
+    Ok(())
+}
+
+/// Removes every unused pip-cache volume pyze manages.
+pub async fn prune(docker: &Docker) -> Result<()> {
+    let mut filters = HashMap::new();
+    filters.insert("label".to_string(), vec![MANAGED_LABEL.to_string()]);
+
+    let response = docker
+        .prune_volumes(Some(PruneVolumesOptions { filters }))
+        .await
+        .wrap_err("Failed to prune volumes")?;
+
+    for name in response.volumes_deleted.unwrap_or_default() {
+        println!("deleted {}", name);
+    }
+    Ok(())
+}